@@ -90,6 +90,286 @@ impl Parse for StateTransition {
     }
 }
 
+// The set of wire formats compiled into the generated `Encoded` enum. Each
+// non-JSON variant pulls in its serde backend as an optional dependency, so
+// it's only emitted when the corresponding cargo feature is enabled on this
+// crate.
+fn encoded_variant_defs() -> Vec<proc_macro2::TokenStream> {
+    let mut variants = vec![quote!(Json(serde_json::Value))];
+    if cfg!(feature = "msgpack") {
+        variants.push(quote!(MessagePack(Vec<u8>)));
+    }
+    if cfg!(feature = "cbor") {
+        variants.push(quote!(Cbor(Vec<u8>)));
+    }
+    if cfg!(feature = "bincode") {
+        variants.push(quote!(Bincode(Vec<u8>)));
+    }
+    variants
+}
+
+// Builds the `match` arms that decode an `Encoded` value of `expr` into
+// whatever type the surrounding `let` binding expects, one arm per format
+// compiled in via `encoded_variant_defs`.
+fn encoded_decode_arms(expr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let mut arms = vec![quote! {
+        Encoded::Json(data) => serde_json::from_value(data).ok().ok_or(RestoreError::InvalidData)?
+    }];
+    if cfg!(feature = "msgpack") {
+        arms.push(quote! {
+            Encoded::MessagePack(data) => rmp_serde::from_slice(&data).ok().ok_or(RestoreError::InvalidData)?
+        });
+    }
+    if cfg!(feature = "cbor") {
+        arms.push(quote! {
+            Encoded::Cbor(data) => serde_cbor::from_slice(&data).ok().ok_or(RestoreError::InvalidData)?
+        });
+    }
+    if cfg!(feature = "bincode") {
+        arms.push(quote! {
+            Encoded::Bincode(data) => bincode::deserialize(&data).ok().ok_or(RestoreError::InvalidData)?
+        });
+    }
+    quote! {
+        match #expr {
+            #(#arms),*
+        }
+    }
+}
+
+// Unit-only mirror of `Encoded`'s compiled-in variants, used to pick a wire
+// format for outgoing data without committing to a representation yet.
+fn encoded_format_variant_defs() -> Vec<proc_macro2::TokenStream> {
+    let mut variants = vec![quote!(Json)];
+    if cfg!(feature = "msgpack") {
+        variants.push(quote!(MessagePack));
+    }
+    if cfg!(feature = "cbor") {
+        variants.push(quote!(Cbor));
+    }
+    if cfg!(feature = "bincode") {
+        variants.push(quote!(Bincode));
+    }
+    variants
+}
+
+// Builds the `match` arms that serialize `value_expr` into an `Encoded` of
+// the format named by `format_expr`, the encode-side counterpart of
+// `encoded_decode_arms`. This is what `Observer` impls call from
+// `on_init`/`on_transition` to turn the typed data they're handed into
+// bytes tagged with the codec that produced them.
+fn encoded_encode_arms(format_expr: &proc_macro2::TokenStream, value_expr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let mut arms = vec![quote! {
+        EncodedFormat::Json => serde_json::to_value(#value_expr).map(Encoded::Json).map_err(|_| EncodeError::Failed)?
+    }];
+    if cfg!(feature = "msgpack") {
+        arms.push(quote! {
+            EncodedFormat::MessagePack => rmp_serde::to_vec(#value_expr).map(Encoded::MessagePack).map_err(|_| EncodeError::Failed)?
+        });
+    }
+    if cfg!(feature = "cbor") {
+        arms.push(quote! {
+            EncodedFormat::Cbor => serde_cbor::to_vec(#value_expr).map(Encoded::Cbor).map_err(|_| EncodeError::Failed)?
+        });
+    }
+    if cfg!(feature = "bincode") {
+        arms.push(quote! {
+            EncodedFormat::Bincode => bincode::serialize(#value_expr).map(Encoded::Bincode).map_err(|_| EncodeError::Failed)?
+        });
+    }
+    quote! {
+        match #format_expr {
+            #(#arms),*
+        }
+    }
+}
+
+// `Encoded::tag`/`into_bytes`/`from_tagged_bytes`, the round-trip a byte-
+// oriented store (a BYTEA column, a binary KV store) needs: a short string
+// naming the codec alongside the raw bytes, and a way back from that pair
+// to an `Encoded`. The `postgres` module built on top of this is the first
+// consumer.
+fn encoded_tag_arms() -> proc_macro2::TokenStream {
+    let mut arms = vec![quote!(Encoded::Json(_) => "json")];
+    if cfg!(feature = "msgpack") {
+        arms.push(quote!(Encoded::MessagePack(_) => "msgpack"));
+    }
+    if cfg!(feature = "cbor") {
+        arms.push(quote!(Encoded::Cbor(_) => "cbor"));
+    }
+    if cfg!(feature = "bincode") {
+        arms.push(quote!(Encoded::Bincode(_) => "bincode"));
+    }
+    quote! {
+        match self {
+            #(#arms),*
+        }
+    }
+}
+
+fn encoded_into_bytes_arms() -> proc_macro2::TokenStream {
+    let mut arms = vec![quote!(Encoded::Json(v) => serde_json::to_vec(&v).unwrap_or_default())];
+    if cfg!(feature = "msgpack") {
+        arms.push(quote!(Encoded::MessagePack(b) => b));
+    }
+    if cfg!(feature = "cbor") {
+        arms.push(quote!(Encoded::Cbor(b) => b));
+    }
+    if cfg!(feature = "bincode") {
+        arms.push(quote!(Encoded::Bincode(b) => b));
+    }
+    quote! {
+        match self {
+            #(#arms),*
+        }
+    }
+}
+
+fn encoded_from_tagged_bytes_arms() -> proc_macro2::TokenStream {
+    let mut arms = vec![quote! {
+        "json" => serde_json::from_slice(&bytes).ok().map(Encoded::Json).ok_or(RestoreError::InvalidData)
+    }];
+    if cfg!(feature = "msgpack") {
+        arms.push(quote!("msgpack" => Ok(Encoded::MessagePack(bytes))));
+    }
+    if cfg!(feature = "cbor") {
+        arms.push(quote!("cbor" => Ok(Encoded::Cbor(bytes))));
+    }
+    if cfg!(feature = "bincode") {
+        arms.push(quote!("bincode" => Ok(Encoded::Bincode(bytes))));
+    }
+    quote! {
+        match tag {
+            #(#arms,)*
+            _ => Err(RestoreError::InvalidData)
+        }
+    }
+}
+
+// Emits `#[tracing::instrument(...)]` when the `tracing` cargo feature is
+// enabled on this crate, or nothing otherwise — the same host-side
+// `cfg!()` gating used for `postgres_module`/`broadcast_module`, since
+// this has to vary with *this* crate's features rather than the
+// downstream crate's. Spans nest via tracing's ambient span stack, so
+// instrumenting both a transition method and the `Observer::on_transition`
+// impls it calls is enough to attribute listener work to the transition
+// that triggered it without any manual span plumbing.
+fn tracing_attr(fields: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    if cfg!(feature = "tracing") {
+        quote! {
+            #[tracing::instrument(level = "debug", skip_all, fields(#fields))]
+        }
+    } else {
+        quote!()
+    }
+}
+
+// Semantic checks over the parsed `Machine` that the grammar alone can't
+// express: dangling transition targets, duplicate events within a state,
+// and a missing/ambiguous init state. Errors are collected and combined so
+// the user sees every problem in one pass instead of fixing them one at a
+// time, each pointing at the offending identifier via `Ident::span()`.
+fn validate_machine(m: &Machine, state_names: &[&Ident]) -> Result<()> {
+    let name_set: HashSet<String> = state_names.iter().map(|n| n.to_string()).collect();
+    let mut errors: Vec<syn::Error> = Vec::new();
+
+    let mut init_count = 0;
+    for state in m.states.iter() {
+        if state.init {
+            init_count += 1;
+        }
+
+        let mut seen_events: HashSet<String> = HashSet::new();
+        for transition in state.transitions.iter() {
+            let event_name = transition.event.to_string();
+            if !seen_events.insert(event_name.clone()) {
+                errors.push(syn::Error::new(transition.event.span(), format!("duplicate event `{}` in state `{}`", event_name, state.name)));
+            }
+
+            if !name_set.contains(&transition.next_state.to_string()) {
+                errors.push(syn::Error::new(transition.next_state.span(), format!("transition targets undeclared state `{}`", transition.next_state)));
+            }
+        }
+    }
+
+    if init_count == 0 {
+        errors.push(syn::Error::new(m.name.span(), "machine must declare an `init` state"));
+    } else if init_count > 1 {
+        errors.push(syn::Error::new(m.name.span(), "machine must declare exactly one `init` state"));
+    }
+
+    // Event names are shared across the generated `Event` enum regardless of
+    // which state declares them (chunk0-3), so two states reusing the same
+    // event name must agree on the payload shape (the target state's
+    // associated data type) or `Event`'s variant shape and a later
+    // `handle_arms` match arm for the same name disagree on unit vs. tuple.
+    // `seen_events` above only catches a duplicate within a single state, not
+    // this cross-state collision, so it's checked separately here.
+    let associated_data_types: HashMap<String, Option<String>> = m.states.iter()
+        .map(|s| (s.name.to_string(), s.associated_data_type.as_ref().map(|dt| dt.to_string())))
+        .collect();
+
+    let mut event_payloads: HashMap<String, (Option<String>, String)> = HashMap::new();
+    for state in m.states.iter() {
+        for transition in state.transitions.iter() {
+            let target = transition.next_state.to_string();
+            if !name_set.contains(&target) {
+                continue;
+            }
+
+            let payload = associated_data_types.get(&target).cloned().flatten();
+            let event_name = transition.event.to_string();
+            match event_payloads.get(&event_name) {
+                Some((first_payload, first_target)) if first_payload != &payload => {
+                    errors.push(syn::Error::new(
+                        transition.event.span(),
+                        format!(
+                            "event `{}` targets `{}` here but `{}` elsewhere, with a different associated data type; every transition sharing an event name must agree on the payload shape",
+                            event_name, target, first_target
+                        )
+                    ));
+                },
+                Some(_) => {},
+                None => {
+                    event_payloads.insert(event_name, (payload, target));
+                }
+            }
+        }
+    }
+
+    // Unreachable-state check via BFS over the transition adjacency map; only
+    // meaningful once the graph itself is well-formed.
+    if errors.is_empty() {
+        let mut adjacency: HashMap<String, Vec<String>> = HashMap::new();
+        for state in m.states.iter() {
+            let targets = state.transitions.iter().map(|t| t.next_state.to_string()).collect();
+            adjacency.insert(state.name.to_string(), targets);
+        }
+
+        let mut reachable: HashSet<String> = HashSet::new();
+        let mut queue: Vec<String> = m.states.iter().filter(|s| s.init).map(|s| s.name.to_string()).collect();
+        while let Some(current) = queue.pop() {
+            if !reachable.insert(current.clone()) {
+                continue;
+            }
+            if let Some(targets) = adjacency.get(&current) {
+                queue.extend(targets.iter().cloned());
+            }
+        }
+
+        for state in m.states.iter() {
+            if !reachable.contains(&state.name.to_string()) {
+                errors.push(syn::Error::new(state.name.span(), format!("state `{}` is unreachable from any init state", state.name)));
+            }
+        }
+    }
+
+    match errors.into_iter().reduce(|mut acc, e| { acc.combine(e); acc }) {
+        Some(e) => Err(e),
+        None => Ok(())
+    }
+}
+
 #[proc_macro]
 pub fn statemachine(input: TokenStream) -> TokenStream {
     let m = parse_macro_input!(input as Machine);
@@ -107,10 +387,282 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
 
     let state_names: Vec<&Ident> = m.states.iter().map(|x| &x.name).collect();
 
+    if let Err(e) = validate_machine(&m, &state_names) {
+        return e.to_compile_error().into();
+    }
+
+    let encoded_variants = encoded_variant_defs();
+    let encoded_format_variants = encoded_format_variant_defs();
+    let encode_match = encoded_encode_arms(&quote!(format), &quote!(value));
+    let encoded_tag_match = encoded_tag_arms();
+    let encoded_into_bytes_match = encoded_into_bytes_arms();
+    let encoded_from_tagged_bytes_match = encoded_from_tagged_bytes_arms();
+
     let parent_name = &m.name;
     let wrapped_type = format_ident!("{}{}", "Wrapped", parent_name);
     let shared_data_type = &m.shared_data_type;
-    
+
+    let on_transition_span = tracing_attr(quote! {
+        machine = stringify!(#parent_name), id = %id, from = ?from, to = ?to, expected_version, new_version
+    });
+
+    // `BroadcastObserver::on_transition` doesn't use `expected_version`/
+    // `new_version` in its body (it has nothing to CAS against), so its
+    // params stay underscore-prefixed to avoid an `unused_variables` error
+    // when `tracing` is off. The shorthand `fields(expected_version, ...)`
+    // above requires an exact-name variable in scope, which an underscored
+    // param isn't, so this impl gets its own span recording them via
+    // `field = expr` against the underscored names instead.
+    let broadcast_on_transition_span = tracing_attr(quote! {
+        machine = stringify!(#parent_name), id = %id, from = ?from, to = ?to, expected_version = _expected_version, new_version = _new_version
+    });
+
+    // A ready-made `Observer`/`Retriever` pair over a deadpool-pooled
+    // Postgres connection, generated per-invocation since `Observer` and
+    // `Retriever` themselves are generated per-invocation. Opt in with the
+    // `postgres` cargo feature on this crate.
+    let postgres_module = if cfg!(feature = "postgres") {
+        let table_name = format!("{}_states", parent_name.to_string().to_case(Case::Snake));
+        let transitions_table_name = format!("{}_transitions", parent_name.to_string().to_case(Case::Snake));
+
+        let migration_sql = format!(
+            "CREATE TABLE IF NOT EXISTS {table} (\n    id TEXT PRIMARY KEY,\n    state TEXT NOT NULL,\n    data BYTEA,\n    data_format TEXT,\n    state_data BYTEA,\n    state_data_format TEXT,\n    version BIGINT NOT NULL\n);\nCREATE TABLE IF NOT EXISTS {transitions} (\n    id SERIAL PRIMARY KEY,\n    machine_id TEXT NOT NULL REFERENCES {table}(id),\n    from_state TEXT,\n    to_state TEXT NOT NULL,\n    occurred_at TIMESTAMPTZ NOT NULL DEFAULT now()\n);",
+            table = table_name,
+            transitions = transitions_table_name
+        );
+        let insert_sql = format!("INSERT INTO {} (id, state, data, data_format, state_data, state_data_format, version) VALUES ($1, $2, $3, $4, $5, $6, 1)", table_name);
+        let update_sql = format!("UPDATE {} SET state = $2, data = $3, data_format = $4, state_data = $5, state_data_format = $6, version = $8 WHERE id = $1 AND version = $7", table_name);
+        let insert_transition_sql = format!("INSERT INTO {} (machine_id, from_state, to_state) VALUES ($1, $2, $3)", transitions_table_name);
+        let select_sql = format!("SELECT state, data, data_format, state_data, state_data_format, version FROM {} WHERE id = $1", table_name);
+
+        quote! {
+            /// Ready-made Postgres persistence for this machine, backed by a
+            /// `deadpool_postgres::Pool`. Run `MIGRATION_SQL` once (it's
+            /// idempotent) before using `PgObserver`.
+            pub mod pg {
+                use super::*;
+
+                pub const MIGRATION_SQL: &str = #migration_sql;
+
+                /// Errors `PgObserver` can surface: either the Postgres pool/driver,
+                /// or `encode()` failing on a payload (e.g. an unserializable shape
+                /// under `bincode`). Both used to be swallowed into a silent NULL
+                /// column; surfacing them here means a bad write fails loudly at
+                /// `on_init`/`on_transition` instead of at a later, unrelated-looking
+                /// `restore()` call.
+                #[derive(Debug)]
+                pub enum PgObserverError {
+                    Pool(deadpool_postgres::PoolError),
+                    Pg(tokio_postgres::Error),
+                    Encode(EncodeError)
+                }
+
+                impl From<deadpool_postgres::PoolError> for PgObserverError {
+                    fn from(e: deadpool_postgres::PoolError) -> Self {
+                        PgObserverError::Pool(e)
+                    }
+                }
+
+                impl From<tokio_postgres::Error> for PgObserverError {
+                    fn from(e: tokio_postgres::Error) -> Self {
+                        PgObserverError::Pg(e)
+                    }
+                }
+
+                impl From<EncodeError> for PgObserverError {
+                    fn from(e: EncodeError) -> Self {
+                        PgObserverError::Encode(e)
+                    }
+                }
+
+                pub struct PgObserver {
+                    pool: deadpool_postgres::Pool,
+                    format: EncodedFormat
+                }
+
+                impl PgObserver {
+                    pub fn new(pool: deadpool_postgres::Pool, format: EncodedFormat) -> Self {
+                        Self { pool, format }
+                    }
+
+                    fn encode_column<T: Serialize>(&self, value: &Option<T>) -> Result<(Option<String>, Option<Vec<u8>>), EncodeError> {
+                        match value {
+                            Some(v) => {
+                                let encoded = encode(self.format, v)?;
+                                Ok((Some(encoded.tag().to_string()), Some(encoded.into_bytes())))
+                            },
+                            None => Ok((None, None))
+                        }
+                    }
+
+                    fn decode_column(format: Option<String>, bytes: Option<Vec<u8>>) -> Result<Option<Encoded>, RestoreError> {
+                        match (format, bytes) {
+                            (Some(format), Some(bytes)) => Encoded::from_tagged_bytes(&format, bytes).map(Some),
+                            _ => Ok(None)
+                        }
+                    }
+                }
+
+                #[async_trait]
+                impl<S: Send> Observer<S> for PgObserver {
+                    type Error = PgObserverError;
+
+                    async fn on_init<T: Serialize + Send, U: Serialize + Send>(&mut self, _ctx: &mut S, id: Option<String>, to: State, data: Option<T>, state_data: Option<U>) -> Result<Option<String>, Self::Error> {
+                        let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                        let (data_format, data_bytes) = self.encode_column(&data)?;
+                        let (state_data_format, state_data_bytes) = self.encode_column(&state_data)?;
+
+                        let client = self.pool.get().await?;
+                        client.execute(#insert_sql, &[&id, &to.to_string(), &data_bytes, &data_format, &state_data_bytes, &state_data_format]).await?;
+
+                        Ok(Some(id))
+                    }
+
+                    #on_transition_span
+                    async fn on_transition<T: Serialize + Send, U: Serialize + Send>(&mut self, _ctx: &mut S, id: &str, from: State, to: State, data: Option<T>, state_data: Option<U>, expected_version: u64, new_version: u64) -> Result<bool, Self::Error> {
+                        let (data_format, data_bytes) = self.encode_column(&data)?;
+                        let (state_data_format, state_data_bytes) = self.encode_column(&state_data)?;
+
+                        // The CAS update and the audit-log insert must land together:
+                        // a crash between two standalone `execute()` calls would leave
+                        // the transitions table silently missing an entry for a
+                        // transition that was actually applied.
+                        let mut client = self.pool.get().await?;
+                        let txn = client.transaction().await?;
+
+                        let updated = txn.execute(#update_sql, &[&id.to_string(), &to.to_string(), &data_bytes, &data_format, &state_data_bytes, &state_data_format, &(expected_version as i64), &(new_version as i64)]).await?;
+                        if updated == 0 {
+                            txn.rollback().await?;
+                            return Ok(false);
+                        }
+                        txn.execute(#insert_transition_sql, &[&id.to_string(), &from.to_string(), &to.to_string()]).await?;
+
+                        txn.commit().await?;
+                        Ok(true)
+                    }
+                }
+
+                #[async_trait]
+                impl<S: Send> Retriever<S> for PgObserver {
+                    type RetrieverError = PgObserverError;
+
+                    async fn on_retrieve(&mut self, _ctx: &mut S, id: &str) -> Result<(String, Option<Encoded>, Option<Encoded>, u64), Self::RetrieverError> {
+                        let client = self.pool.get().await?;
+                        let row = client.query_one(#select_sql, &[&id.to_string()]).await?;
+
+                        let state: String = row.get(0);
+                        let data = PgObserver::decode_column(row.get(2), row.get(1)).unwrap_or(None);
+                        let state_data = PgObserver::decode_column(row.get(4), row.get(3)).unwrap_or(None);
+                        let version: i64 = row.get(5);
+
+                        Ok((state, data, state_data, version as u64))
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    // A broadcast-channel `Observer` dashboards can subscribe to for live
+    // progress, plus a `FanoutObserver` combinator so it composes with a
+    // user's existing (e.g. persistence) observer instead of replacing it.
+    let broadcast_module = if cfg!(feature = "broadcast") {
+        quote! {
+            #[derive(Debug, Clone)]
+            pub struct TransitionEvent {
+                pub id: String,
+                pub from: Option<State>,
+                pub to: State,
+                pub timestamp_millis: u128
+            }
+
+            fn now_millis() -> u128 {
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0)
+            }
+
+            /// Emits a `TransitionEvent` on every `on_init`/`on_transition` over a
+            /// `tokio::sync::broadcast` channel, e.g. for an axum `Sse` response.
+            pub struct BroadcastObserver {
+                sender: tokio::sync::broadcast::Sender<TransitionEvent>
+            }
+
+            impl BroadcastObserver {
+                pub fn new(capacity: usize) -> Self {
+                    let (sender, _) = tokio::sync::broadcast::channel(capacity);
+                    Self { sender }
+                }
+
+                pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<TransitionEvent> {
+                    self.sender.subscribe()
+                }
+            }
+
+            #[async_trait]
+            impl<S: Send> Observer<S> for BroadcastObserver {
+                type Error = std::convert::Infallible;
+
+                async fn on_init<T: Serialize + Send, U: Serialize + Send>(&mut self, _ctx: &mut S, id: Option<String>, to: State, _data: Option<T>, _state_data: Option<U>) -> Result<Option<String>, Self::Error> {
+                    if let Some(id) = &id {
+                        let _ = self.sender.send(TransitionEvent { id: id.clone(), from: None, to, timestamp_millis: now_millis() });
+                    }
+                    Ok(id)
+                }
+
+                #broadcast_on_transition_span
+                async fn on_transition<T: Serialize + Send, U: Serialize + Send>(&mut self, _ctx: &mut S, id: &str, from: State, to: State, _data: Option<T>, _state_data: Option<U>, _expected_version: u64, _new_version: u64) -> Result<bool, Self::Error> {
+                    let _ = self.sender.send(TransitionEvent { id: id.to_string(), from: Some(from), to, timestamp_millis: now_millis() });
+                    Ok(true)
+                }
+            }
+
+            /// Runs `first` and `second` on every `on_init`/`on_transition` so e.g.
+            /// persistence and streaming both observe each transition. Because
+            /// `Observer`'s data parameters carry no `Clone` bound, only `first`
+            /// receives the typed payload; `second` only sees transition metadata
+            /// (id/state) and should be something like `BroadcastObserver` that
+            /// doesn't need it. `on_transition`'s CAS result is the AND of both
+            /// sides: if either observer reports (or, for `second`, errors on)
+            /// a version conflict, the whole transition is reported as not
+            /// applied, so composing two CAS-aware observers (e.g. two
+            /// `PgObserver`s, or `FanoutObserver::new(pg, another_pg)`) can't have
+            /// one side silently diverge from the other. `second`'s non-boolean
+            /// errors can't be represented in `Self::Error` (which is `A::Error`),
+            /// so they're treated conservatively as a conflict (`false`) rather
+            /// than swallowed into success.
+            pub struct FanoutObserver<A, B> {
+                first: A,
+                second: B
+            }
+
+            impl<A, B> FanoutObserver<A, B> {
+                pub fn new(first: A, second: B) -> Self {
+                    Self { first, second }
+                }
+            }
+
+            #[async_trait]
+            impl<S: Send, A: Observer<S> + Send, B: Observer<S> + Send> Observer<S> for FanoutObserver<A, B> {
+                type Error = A::Error;
+
+                async fn on_init<T: Serialize + Send, U: Serialize + Send>(&mut self, ctx: &mut S, id: Option<String>, to: State, data: Option<T>, state_data: Option<U>) -> Result<Option<String>, Self::Error> {
+                    let id = self.first.on_init(ctx, id, to, data, state_data).await?;
+                    let _ = self.second.on_init(ctx, id.clone(), to, Option::<()>::None, Option::<()>::None).await;
+                    Ok(id)
+                }
+
+                #on_transition_span
+                async fn on_transition<T: Serialize + Send, U: Serialize + Send>(&mut self, ctx: &mut S, id: &str, from: State, to: State, data: Option<T>, state_data: Option<U>, expected_version: u64, new_version: u64) -> Result<bool, Self::Error> {
+                    let applied = self.first.on_transition(ctx, id, from, to, data, state_data, expected_version, new_version).await?;
+                    let second_applied = self.second.on_transition(ctx, id, from, to, Option::<()>::None, Option::<()>::None, expected_version, new_version).await.unwrap_or(false);
+                    Ok(applied && second_applied)
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     let state_structs = m.states.iter().map(|x| {
         let state_name = &x.name;
         let data_type = &x.associated_data_type;
@@ -180,25 +732,44 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                 quote!(State::#state_name)
             };
 
+            let cas = quote! {
+                let expected_version = self.version;
+                let new_version = expected_version + 1;
+            };
+
+            let transition_span = tracing_attr(quote! {
+                machine = stringify!(#parent_name), id = %self.id, from = ?State::#state_name, to = ?State::#next_state_name
+            });
+
             match arg {
                 Some(a) => match shared_data_type {
                     Some(_) => quote! {
                         impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
+                            #transition_span
                             pub async fn #event(mut self, ctx: &mut S, data: #a) -> Result<#parent_name<#next_state_name, S, T>, TransitionError<T::Error>> {
-                                self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Some(&self.data), Some(&data)).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                #cas
+                                let applied = self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Some(&self.data), Some(&data), expected_version, new_version).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                if !applied {
+                                    return Err(TransitionError::VersionConflict);
+                                }
                                 #exit_call
                                 self.observer.#enter_fn_name(ctx, &self.id, #enter_from_type, &self.data, &data).await.map_err(|e| TransitionError::ObserverError(e))?;
-                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(data), self.data))
+                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(data), self.data, new_version))
                             }
                         }
                     },
                     None => quote! {
                         impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
+                            #transition_span
                             pub async fn #event(mut self, ctx: &mut S, data: #a) -> Result<#parent_name<#next_state_name, S, T>, TransitionError<T::Error>> {
-                                self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Option::<()>::None, Some(&data)).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                #cas
+                                let applied = self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Option::<()>::None, Some(&data), expected_version, new_version).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                if !applied {
+                                    return Err(TransitionError::VersionConflict);
+                                }
                                 #exit_call
                                 self.observer.#enter_fn_name(ctx, &self.id, #enter_from_type, &data).await.map_err(|e| TransitionError::ObserverError(e))?;
-                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(data)))
+                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(data), new_version))
                             }
                         }
                     }
@@ -206,21 +777,31 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                 None => match shared_data_type {
                     Some(_) => quote! {
                         impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
+                            #transition_span
                             pub async fn #event(mut self, ctx: &mut S) -> Result<#parent_name<#next_state_name, S, T>, TransitionError<T::Error>> {
-                                self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Some(&self.data), Option::<()>::None).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                #cas
+                                let applied = self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Some(&self.data), Option::<()>::None, expected_version, new_version).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                if !applied {
+                                    return Err(TransitionError::VersionConflict);
+                                }
                                 #exit_call
                                 self.observer.#enter_fn_name(ctx, &self.id, #enter_from_type, &self.data).await.map_err(|e| TransitionError::ObserverError(e))?;
-                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(), self.data))
+                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(), self.data, new_version))
                             }
                         }
                     },
                     None => quote! {
                         impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
+                            #transition_span
                             pub async fn #event(mut self, ctx: &mut S) -> Result<#parent_name<#next_state_name, S, T>, TransitionError<T::Error>> {
-                                self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Option::<()>::None, Option::<()>::None).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                #cas
+                                let applied = self.observer.on_transition(ctx, &self.id, State::#state_name, State::#next_state_name, Option::<()>::None, Option::<()>::None, expected_version, new_version).await.map_err(|e| TransitionError::ObserverError(e))?;
+                                if !applied {
+                                    return Err(TransitionError::VersionConflict);
+                                }
                                 #exit_call
                                 self.observer.#enter_fn_name(ctx, &self.id, #enter_from_type).await.map_err(|e| TransitionError::ObserverError(e))?;
-                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new()))
+                                Ok(#parent_name::<#next_state_name, S, T>::new(self.observer, self.id, #next_state_name::new(), new_version))
                             }
                         }
                     }
@@ -235,6 +816,87 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
         }
     });
 
+    // One `Event` variant per distinct event name across all states, carrying
+    // the target state's associated data where the transition it drives has
+    // one. Built once here so the enum, its `name()` accessor, and the
+    // `handle` dispatch below all agree on the same variant identifiers.
+    let mut event_entries: Vec<(&Ident, Option<&Ident>)> = Vec::new();
+    let mut seen_event_names: HashSet<String> = HashSet::new();
+    for state in m.states.iter() {
+        for transition in state.transitions.iter() {
+            if seen_event_names.insert(transition.event.to_string()) {
+                let arg = state_data_types.get(&transition.next_state).copied();
+                event_entries.push((&transition.event, arg));
+            }
+        }
+    }
+
+    let event_variant_idents: Vec<Ident> = event_entries.iter()
+        .map(|(event, _)| format_ident!("{}", event.to_string().to_case(Case::Pascal)))
+        .collect();
+
+    let event_variants = event_entries.iter().zip(event_variant_idents.iter()).map(|((_, arg), variant)| {
+        match arg {
+            Some(a) => quote!(#variant(#a)),
+            None => quote!(#variant)
+        }
+    });
+
+    let event_name_arms = event_entries.iter().zip(event_variant_idents.iter()).map(|((event, arg), variant)| {
+        match arg {
+            Some(_) => quote!(Event::#variant(..) => stringify!(#event)),
+            None => quote!(Event::#variant => stringify!(#event))
+        }
+    });
+
+    let handle_arms = m.states.iter().flat_map(|x| {
+        let state_name = &x.name;
+        x.transitions.iter().map(|y| {
+            let event = &y.event;
+            let next_state_name = &y.next_state;
+            let arg = state_data_types.get(next_state_name);
+            let variant = format_ident!("{}", event.to_string().to_case(Case::Pascal));
+
+            match arg {
+                Some(_) => quote! {
+                    (#wrapped_type::#state_name(s), Event::#variant(data)) => s.#event(ctx, data).await.map(#wrapped_type::#next_state_name)
+                },
+                None => quote! {
+                    (#wrapped_type::#state_name(s), Event::#variant) => s.#event(ctx).await.map(#wrapped_type::#next_state_name)
+                }
+            }
+        }).collect::<Vec<_>>()
+    });
+
+    // Runtime introspection over the transition graph the macro already
+    // knows about at expansion time, so callers can validate an event
+    // against the current state (or build a dashboard) without hard-coding
+    // the graph themselves.
+    let state_from_str_arms = m.states.iter().map(|x| {
+        let state_name = &x.name;
+        quote!(stringify!(#state_name) => Some(State::#state_name))
+    });
+
+    let state_valid_events_arms = m.states.iter().map(|x| {
+        let state_name = &x.name;
+        let events: Vec<&Ident> = x.transitions.iter().map(|t| &t.event).collect();
+        quote! {
+            State::#state_name => &[#(stringify!(#events)),*]
+        }
+    });
+
+    let state_transitions_arms = m.states.iter().map(|x| {
+        let state_name = &x.name;
+        let entries = x.transitions.iter().map(|t| {
+            let event = &t.event;
+            let next_state_name = &t.next_state;
+            quote!((stringify!(#event), State::#next_state_name))
+        });
+        quote! {
+            State::#state_name => &[#(#entries),*]
+        }
+    });
+
     let parent_state_impls = m.states.iter().map(|x| {
         let state_name = &x.name;
         let enter_fn_name = format_ident!("{}_{}", "on_enter", state_name.to_string().to_case(Case::Snake));
@@ -243,18 +905,23 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
             pub fn id(&self) -> &str {
                 &self.id
             }
+
+            pub fn version(&self) -> u64 {
+                self.version
+            }
         };
 
         match shared_data_type {
             Some(sdt) => {
                 let constructor = quote! {
                     impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
-                        fn new(observer: T, id: String, state: #state_name, data: #sdt) -> Self {
+                        fn new(observer: T, id: String, state: #state_name, data: #sdt, version: u64) -> Self {
                             Self {
                                 observer,
                                 id,
                                 state,
                                 data,
+                                version,
                                 phantom: PhantomData
                             }
                         }
@@ -272,23 +939,23 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                     true => match &x.associated_data_type {
                         Some(dt) => quote! {
                             #constructor
-    
+
                             impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
                                 pub async fn init(ctx: &mut S, mut observer: T, id: Option<String>, data: #sdt, state_data: #dt) -> Result<Self, InitError<T::Error>> {
                                     let id = observer.on_init(ctx, id, State::#state_name, Some(&data), Some(&state_data)).await.map_err(|e| InitError::ObserverError(e))?.ok_or(InitError::EmptyId)?;
                                     observer.#enter_fn_name(ctx, &id, None, &data, &state_data).await.map_err(|e| InitError::ObserverError(e))?;
-                                    Ok(Self::new(observer, id, #state_name::new(state_data), data))
+                                    Ok(Self::new(observer, id, #state_name::new(state_data), data, 1))
                                 }
                             }
                         },
                         None => quote! {
                             #constructor
-    
+
                             impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
                                 pub async fn init(ctx: &mut S, mut observer: T, id: Option<String>, data: #sdt) -> Result<Self, InitError<T::Error>> {
                                     let id = observer.on_init(ctx, id, State::#state_name, Some(&data), Option::<()>::None).await.map_err(|e| InitError::ObserverError(e))?.ok_or(InitError::EmptyId)?;
                                     observer.#enter_fn_name(ctx, &id, None, &data).await.map_err(|e| InitError::ObserverError(e))?;
-                                    Ok(Self::new(observer, id, #state_name::new(), data))
+                                    Ok(Self::new(observer, id, #state_name::new(), data, 1))
                                 }
                             }
                         }
@@ -298,11 +965,12 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
             None => {
                 let constructor = quote! {
                     impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
-                        fn new(observer: T, id: String, state: #state_name) -> Self {
+                        fn new(observer: T, id: String, state: #state_name, version: u64) -> Self {
                             Self {
                                 observer,
                                 id,
                                 state,
+                                version,
                                 phantom: PhantomData
                             }
                         }
@@ -316,23 +984,23 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                     true => match &x.associated_data_type {
                         Some(dt) => quote! {
                             #constructor
-    
+
                             impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
                                 pub async fn init(ctx: &mut S, mut observer: T, id: Option<String>, state_data: #dt) -> Result<Self, InitError<T::Error>> {
                                     let id = observer.on_init(ctx, id, State::#state_name, Option::<()>::None, Some(&state_data)).await.map_err(|e| InitError::ObserverError(e))?.ok_or(InitError::EmptyId)?;
                                     observer.#enter_fn_name(ctx, &id, None, &state_data).await.map_err(|e| InitError::ObserverError(e))?;
-                                    Ok(Self::new(observer, id, #state_name::new(state_data)))
+                                    Ok(Self::new(observer, id, #state_name::new(state_data), 1))
                                 }
                             }
                         },
                         None => quote! {
                             #constructor
-    
+
                             impl<S: Send, T: Observer<S> + Send> #parent_name<#state_name, S, T> {
                                 pub async fn init(ctx: &mut S, mut observer: T, id: Option<String>) -> Result<Self, InitError<T::Error>> {
                                     let id = observer.on_init(ctx, id, State::#state_name, Option::<()>::None, Option::<()>::None).await.map_err(|e| InitError::ObserverError(e))?.ok_or(InitError::EmptyId)?;
                                     observer.#enter_fn_name(ctx, &id, None).await.map_err(|e| InitError::ObserverError(e))?;
-                                    Ok(Self::new(observer, id, #state_name::new()))
+                                    Ok(Self::new(observer, id, #state_name::new(), 1))
                                 }
                             }
                         }
@@ -349,6 +1017,7 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                 id: String,
                 pub state: S,
                 data: #sdt,
+                version: u64,
                 phantom: PhantomData<T>
             }
         },
@@ -357,6 +1026,7 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                 observer: U,
                 id: String,
                 pub state: S,
+                version: u64,
                 phantom: PhantomData<T>
             }
         }
@@ -368,36 +1038,39 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
 
         let fn_name = format_ident!("{}_{}", "restore", state_name.to_string().to_case(Case::Snake));
 
+        let decode_shared_d = encoded_decode_arms(&quote!(shared_d_enc_some));
+        let decode_state_d = encoded_decode_arms(&quote!(state_d_enc_some));
+
+        let restore_fn_span = tracing_attr(quote! {
+            machine = stringify!(#parent_name), state = stringify!(#state_name), id = %id
+        });
+
         match shared_data_type {
             Some(shared_dt) => {
                 match expected_state_dt {
                     Some(state_dt) => quote! {
-                        async fn #fn_name<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>) -> Result<#wrapped_type<S, T>, RestoreError> {
+                        #restore_fn_span
+                        async fn #fn_name<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>, version: u64) -> Result<#wrapped_type<S, T>, RestoreError> {
                             let shared_d_enc_some = shared_d_enc.ok_or(RestoreError::EmptyData)?;
-                            let shared_d: #shared_dt = match shared_d_enc_some {
-                                Encoded::Json(data) => serde_json::from_value(data).ok().ok_or(RestoreError::InvalidData)?
-                            };
+                            let shared_d: #shared_dt = #decode_shared_d;
 
                             let state_d_enc_some = state_d_enc.ok_or(RestoreError::EmptyData)?;
-                            let state_d: #state_dt = match state_d_enc_some {
-                                Encoded::Json(data) => serde_json::from_value(data).ok().ok_or(RestoreError::InvalidData)?
-                            };
+                            let state_d: #state_dt = #decode_state_d;
 
-                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(state_d), shared_d)))
+                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(state_d), shared_d, version)))
                         }
                     },
                     None => quote! {
-                        async fn #fn_name<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>) -> Result<#wrapped_type<S, T>, RestoreError> {
+                        #restore_fn_span
+                        async fn #fn_name<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>, version: u64) -> Result<#wrapped_type<S, T>, RestoreError> {
                             let shared_d_enc_some = shared_d_enc.ok_or(RestoreError::EmptyData)?;
-                            let shared_d: #shared_dt = match shared_d_enc_some {
-                                Encoded::Json(data) => serde_json::from_value(data).ok().ok_or(RestoreError::InvalidData)?
-                            };
+                            let shared_d: #shared_dt = #decode_shared_d;
 
                             if state_d_enc.is_some() {
                                 return Err(RestoreError::UnexpectedData)
                             };
 
-                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(), shared_d)))
+                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(), shared_d, version)))
                         }
                     }
                 }
@@ -405,21 +1078,21 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
             None => {
                 match expected_state_dt {
                     Some(state_dt) => quote! {
-                        async fn #fn_name<S: Send, T: Observer + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>) -> Result<#wrapped_type<S, T>, RestoreError> {
+                        #restore_fn_span
+                        async fn #fn_name<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>, version: u64) -> Result<#wrapped_type<S, T>, RestoreError> {
                             if shared_d_enc.is_some() {
                                 return Err(RestoreError::UnexpectedData)
                             };
 
                             let state_d_enc_some = state_d_enc.ok_or(RestoreError::EmptyData)?;
-                            let state_d: #state_dt = match state_d_enc_some {
-                                Encoded::Json(data) => serde_json::from_value(data).ok().ok_or(RestoreError::InvalidData)?
-                            };
+                            let state_d: #state_dt = #decode_state_d;
 
-                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(state_d))))
+                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(state_d), version)))
                         }
                     },
                     None => quote! {
-                        async fn #fn_name<S: Send, T: Observer + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>) -> Result<#wrapped_type<S, T>, RestoreError> {
+                        #restore_fn_span
+                        async fn #fn_name<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, shared_d_enc: Option<Encoded>, state_d_enc: Option<Encoded>, version: u64) -> Result<#wrapped_type<S, T>, RestoreError> {
                             if shared_d_enc.is_some() {
                                 return Err(RestoreError::UnexpectedData)
                             };
@@ -428,7 +1101,7 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                                 return Err(RestoreError::UnexpectedData)
                             };
 
-                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new())))
+                            Ok(#wrapped_type::#state_name(#parent_name::<#state_name, S, T>::new(observer, id, #state_name::new(), version)))
                         }
                     }
                 }
@@ -439,7 +1112,18 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
     let restore_arms = m.states.iter().map(|x| {
         let state_name = &x.name;
         let fn_name = format_ident!("{}_{}", "restore", state_name.to_string().to_case(Case::Snake));
-        quote!(stringify!(#state_name) => #fn_name(observer, id, data, state_data).await)
+        quote!(stringify!(#state_name) => #fn_name(observer, id, data, state_data, version).await)
+    });
+
+    // `replay` needs to hand the same observer from one in-memory `restore`
+    // call to the next as it folds an event log forward, but the observer
+    // lives behind a private field on whichever concrete `#parent_name<...>`
+    // the current `#wrapped_type` variant wraps. This unwraps it generically,
+    // one match arm per state, mirroring how `state()` is generated.
+    let unwrap_observer_fn = format_ident!("unwrap_observer_{}", parent_name.to_string().to_case(Case::Snake));
+    let replay_unwrap_arms = m.states.iter().map(|x| {
+        let state_name = &x.name;
+        quote!(#wrapped_type::#state_name(s) => (s.observer, s.id))
     });
 
     let listeners = m.states.iter().map(|x| {
@@ -495,6 +1179,9 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
 
     });
 
+    let restore_span = tracing_attr(quote!(machine = stringify!(#parent_name), id = %id, state = %state_string));
+    let retrieve_span = tracing_attr(quote!(machine = stringify!(#parent_name), id = %id));
+
     let out = quote! {
         #[derive(Debug)]
         pub enum InitError<T> {
@@ -504,7 +1191,9 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
 
         #[derive(Debug)]
         pub enum TransitionError<T> {
-            ObserverError(T)
+            ObserverError(T),
+            InvalidEvent { state: State, event: &'static str },
+            VersionConflict
         }
 
         #[derive(Debug)]
@@ -521,6 +1210,7 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
             RetrieverError(T)
         }
         
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         pub enum State {
             #(#state_names),*
         }
@@ -531,6 +1221,33 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                     #(State::#state_names => String::from(stringify!(#state_names))),*
                 }
             }
+
+            pub fn all() -> &'static [State] {
+                &[#(State::#state_names),*]
+            }
+
+            // Named `parse` rather than `from_str` so it doesn't collide with
+            // `std::str::FromStr::from_str`'s naming convention
+            // (`clippy::should_implement_trait`) -- `State` has no need for
+            // the `FromStr` trait itself since nothing parses it via `.parse()`.
+            pub fn parse(s: &str) -> Option<State> {
+                match s {
+                    #(#state_from_str_arms,)*
+                    _ => None
+                }
+            }
+
+            pub fn valid_events(&self) -> &'static [&'static str] {
+                match self {
+                    #(#state_valid_events_arms),*
+                }
+            }
+
+            pub fn transitions(&self) -> &'static [(&'static str, State)] {
+                match self {
+                    #(#state_transitions_arms),*
+                }
+            }
         }
         
         #[async_trait]
@@ -541,7 +1258,21 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
                 Ok(id)
             }
             
-            async fn on_transition<T: Serialize + Send, U: Serialize + Send>(&mut self, ctx: &mut S, id: &str, from: State, to: State, data: Option<T>, state_data: Option<U>) -> Result<(), Self::Error> {
+            // `expected_version`/`new_version` let an implementation persist with a
+            // compare-and-set condition (`... WHERE id = $1 AND version = $expected`).
+            // Return `Ok(false)` when the affected-row count is zero so the caller
+            // surfaces `TransitionError::VersionConflict` instead of silently
+            // clobbering a concurrent writer's update.
+            #on_transition_span
+            async fn on_transition<T: Serialize + Send, U: Serialize + Send>(&mut self, ctx: &mut S, id: &str, from: State, to: State, data: Option<T>, state_data: Option<U>, expected_version: u64, new_version: u64) -> Result<bool, Self::Error> {
+                Ok(true)
+            }
+
+            /// Called by `replay` every `snapshot_every` applied events so a
+            /// backing store can checkpoint at `version` and compact the log
+            /// before it, the way openraft's sled store pairs a log with
+            /// periodic snapshots. A no-op by default.
+            async fn on_snapshot(&mut self, ctx: &mut S, id: &str, version: u64) -> Result<(), Self::Error> {
                 Ok(())
             }
 
@@ -552,7 +1283,7 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
         pub trait Retriever<T: Send> {
             type RetrieverError;
 
-            async fn on_retrieve(&mut self, ctx: &mut T, id: &str) -> Result<(String, Option<Encoded>, Option<Encoded>), Self::RetrieverError>;
+            async fn on_retrieve(&mut self, ctx: &mut T, id: &str) -> Result<(String, Option<Encoded>, Option<Encoded>, u64), Self::RetrieverError>;
         }
 
         #parent_struct
@@ -564,13 +1295,74 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
             #(#state_names(#parent_name<#state_names, S, T>)),*
         }
 
+        pub enum Event {
+            #(#event_variants),*
+        }
+
+        impl Event {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#event_name_arms),*
+                }
+            }
+        }
+
+        impl<S: Send, T: Observer<S> + Send> #wrapped_type<S, T> {
+            pub fn state(&self) -> State {
+                match self {
+                    #(#wrapped_type::#state_names(_) => State::#state_names),*
+                }
+            }
+
+            pub async fn handle(self, ctx: &mut S, event: Event) -> Result<#wrapped_type<S, T>, TransitionError<T::Error>> {
+                let state = self.state();
+                let event_name = event.name();
+                match (self, event) {
+                    #(#handle_arms,)*
+                    _ => Err(TransitionError::InvalidEvent { state, event: event_name })
+                }
+            }
+        }
+
         pub enum Encoded {
-            Json(serde_json::Value)
+            #(#encoded_variants),*
         }
 
+        #[derive(Debug, Clone, Copy)]
+        pub enum EncodedFormat {
+            #(#encoded_format_variants),*
+        }
+
+        #[derive(Debug)]
+        pub enum EncodeError {
+            Failed
+        }
+
+        pub fn encode<T: Serialize>(format: EncodedFormat, value: &T) -> Result<Encoded, EncodeError> {
+            Ok(#encode_match)
+        }
+
+        impl Encoded {
+            pub fn tag(&self) -> &'static str {
+                #encoded_tag_match
+            }
+
+            pub fn into_bytes(self) -> Vec<u8> {
+                #encoded_into_bytes_match
+            }
+
+            pub fn from_tagged_bytes(tag: &str, bytes: Vec<u8>) -> Result<Encoded, RestoreError> {
+                #encoded_from_tagged_bytes_match
+            }
+        }
+
+        #postgres_module
+        #broadcast_module
+
         #(#restore_fns)*
 
-        pub async fn restore<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, state_string: String, data: Option<Encoded>, state_data: Option<Encoded>) -> Result<#wrapped_type<S, T>, RestoreError> {
+        #restore_span
+        pub async fn restore<S: Send, T: Observer<S> + Send>(mut observer: T, id: String, state_string: String, data: Option<Encoded>, state_data: Option<Encoded>, version: u64) -> Result<#wrapped_type<S, T>, RestoreError> {
             let state_str: &str = &state_string;
             match state_str {
                 #(#restore_arms,)*
@@ -578,10 +1370,91 @@ pub fn statemachine(input: TokenStream) -> TokenStream {
             }
         }
 
+        #retrieve_span
         pub async fn retrieve<S: Send, T: Retriever<S> + Observer<S> + Send>(ctx: &mut S, mut retriever: T, id: String) -> Result<#wrapped_type<S, T>, RetrieveError<T::RetrieverError>> {
             let id_str: &str = &id;
-            let (state_string, maybe_data, maybe_state_data) = retriever.on_retrieve(ctx, id_str).await.map_err(|e| RetrieveError::RetrieverError(e))?;
-            restore(retriever, id, state_string, maybe_data, maybe_state_data).await.map_err(|e| RetrieveError::RestoreError(e))
+            let (state_string, maybe_data, maybe_state_data, version) = retriever.on_retrieve(ctx, id_str).await.map_err(|e| RetrieveError::RetrieverError(e))?;
+            restore(retriever, id, state_string, maybe_data, maybe_state_data, version).await.map_err(|e| RetrieveError::RestoreError(e))
+        }
+
+        #[derive(Debug)]
+        pub enum ReplayError<R, O> {
+            EmptyLog,
+            IllegalTransition { from: State, to: State },
+            RestoreError(RestoreError),
+            RetrieverError(R),
+            ObserverError(O)
+        }
+
+        #[async_trait]
+        pub trait EventRetriever<S: Send> {
+            type RetrieverError;
+
+            /// Ordered transition history for `id`, oldest first: the state a
+            /// transition landed in alongside the shared/state data snapshot
+            /// at that point, the same shape `on_retrieve` returns for a
+            /// single row. `replay` checks each step against
+            /// `State::transitions()` and restores the machine from each entry
+            /// in turn; the typestate transition methods and their observer
+            /// hooks are not called.
+            async fn on_retrieve_events(&mut self, ctx: &mut S, id: &str) -> Result<Vec<(State, Option<Encoded>, Option<Encoded>)>, Self::RetrieverError>;
+        }
+
+        fn #unwrap_observer_fn<S: Send, T: Observer<S> + Send>(w: #wrapped_type<S, T>) -> (T, String) {
+            match w {
+                #(#replay_unwrap_arms),*
+            }
+        }
+
+        /// Rebuilds a machine by replaying its transition log instead of
+        /// trusting a single stored snapshot, validating each step against
+        /// `State::transitions()` and failing with `ReplayError::IllegalTransition`
+        /// on a step the declared graph doesn't allow. `snapshot_every`, if
+        /// set, calls `Observer::on_snapshot` every that many applied events
+        /// so a backing store can checkpoint and compact its log, the way
+        /// openraft's sled store pairs a log with periodic snapshots.
+        ///
+        /// `resume_from_version` is the absolute version of the first event
+        /// `on_retrieve_events` hands back. Pass `None` for a from-genesis
+        /// log; pass the checkpointed version when the retriever's tail
+        /// starts after an `on_snapshot` compaction, otherwise every version
+        /// from that point on is mislabeled relative to the real log.
+        pub async fn replay<S: Send, T: EventRetriever<S> + Observer<S> + Send>(ctx: &mut S, mut retriever: T, id: String, snapshot_every: Option<usize>, resume_from_version: Option<u64>) -> Result<#wrapped_type<S, T>, ReplayError<T::RetrieverError, T::Error>> {
+            let id_str: &str = &id;
+            let events = retriever.on_retrieve_events(ctx, id_str).await.map_err(ReplayError::RetrieverError)?;
+            let mut events = events.into_iter();
+
+            // `on_retrieve_events` may hand back a tail that starts at a
+            // compacted `on_snapshot` checkpoint rather than genesis, so the
+            // first event's real version has to come from the caller, not an
+            // assumed `1` -- otherwise every version from here on is
+            // mislabeled, corrupting any CAS check keyed off it downstream.
+            let mut version: u64 = resume_from_version.unwrap_or(1);
+            let (first_state, first_data, first_state_data) = events.next().ok_or(ReplayError::EmptyLog)?;
+            let mut current = restore(retriever, id.clone(), first_state.to_string(), first_data, first_state_data, version).await.map_err(ReplayError::RestoreError)?;
+
+            let mut applied_since_snapshot: usize = 0;
+            for (next_state, data, state_data) in events {
+                let from = current.state();
+                if !from.transitions().iter().any(|(_, to)| *to == next_state) {
+                    return Err(ReplayError::IllegalTransition { from, to: next_state });
+                }
+
+                version += 1;
+                let (mut observer, owned_id) = #unwrap_observer_fn(current);
+
+                applied_since_snapshot += 1;
+                if let Some(every) = snapshot_every {
+                    if applied_since_snapshot >= every {
+                        applied_since_snapshot = 0;
+                        observer.on_snapshot(ctx, &owned_id, version).await.map_err(ReplayError::ObserverError)?;
+                    }
+                }
+
+                current = restore(observer, owned_id, next_state.to_string(), data, state_data, version).await.map_err(ReplayError::RestoreError)?;
+            }
+
+            Ok(current)
         }
     };
 
@@ -0,0 +1,65 @@
+// Covers `BroadcastObserver`/`FanoutObserver` (chunk1-3), in particular the
+// CAS-surfacing fix to `FanoutObserver::on_transition` (a8abb6d): a conflict
+// on either side of the fanout must veto the transition, not just the side
+// that happened to run first. Only compiles with the `broadcast` feature,
+// same as the generated module it exercises -- run with
+// `cargo test --features broadcast`.
+#![cfg(feature = "broadcast")]
+
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use statemachine::statemachine;
+
+statemachine! {
+    Ticket {
+        init Open {
+            close => Closed
+        },
+        Closed {}
+    }
+}
+
+struct RejectingObserver;
+
+#[async_trait]
+impl Observer<()> for RejectingObserver {
+    type Error = std::convert::Infallible;
+
+    async fn on_transition<T: serde::Serialize + Send, U: serde::Serialize + Send>(&mut self, _ctx: &mut (), _id: &str, _from: State, _to: State, _data: Option<T>, _state_data: Option<U>, _expected_version: u64, _new_version: u64) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+#[tokio::test]
+async fn fanout_surfaces_a_conflict_from_either_side() {
+    let mut ctx = ();
+    let broadcaster = BroadcastObserver::new(4);
+    let mut subscriber = broadcaster.subscribe();
+    let fanout = FanoutObserver::new(RejectingObserver, broadcaster);
+
+    let ticket = Ticket::<Open, (), FanoutObserver<RejectingObserver, BroadcastObserver>>::init(&mut ctx, fanout, Some("ticket-1".to_string()))
+        .await
+        .unwrap();
+
+    // `first` (the rejecting observer) still vetoes the transition even
+    // though `second` (the broadcaster) would have happily applied it.
+    let err = ticket.close(&mut ctx).await.err().unwrap();
+    assert!(matches!(err, TransitionError::VersionConflict));
+
+    // `on_init` still reached the broadcast side, since only `on_transition`
+    // was made to reject.
+    let event = subscriber.try_recv().unwrap();
+    assert_eq!(event.id, "ticket-1");
+
+    // With the sides swapped, `second` rejecting vetoes the transition just
+    // the same, even though `first` applied it.
+    let fanout = FanoutObserver::new(BroadcastObserver::new(4), RejectingObserver);
+    let ticket = Ticket::<Open, (), FanoutObserver<BroadcastObserver, RejectingObserver>>::init(&mut ctx, fanout, Some("ticket-2".to_string()))
+        .await
+        .unwrap();
+
+    let err = ticket.close(&mut ctx).await.err().unwrap();
+    assert!(matches!(err, TransitionError::VersionConflict));
+}
@@ -0,0 +1,71 @@
+// Covers `retrieve`/`Retriever` (chunk1-2) end to end. There's no live
+// Postgres instance in this environment to drive `PgObserver` (which
+// implements both `Observer` and `Retriever`) directly, so this exercises
+// the same `Retriever` contract it implements against an in-memory fake --
+// in particular that `retrieve` threads the stored version back into the
+// restored machine rather than resetting it.
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use statemachine::statemachine;
+
+#[derive(Serialize, Deserialize)]
+struct OrderMeta {
+    customer: String
+}
+
+statemachine! {
+    Order: OrderMeta {
+        init Created {
+            pay => Paid
+        },
+        Paid {
+            ship => Shipped
+        },
+        Shipped {}
+    }
+}
+
+struct FakeRow {
+    state: String,
+    data: Option<Encoded>,
+    version: u64
+}
+
+#[async_trait]
+impl Observer<()> for FakeRow {
+    type Error = std::convert::Infallible;
+}
+
+#[async_trait]
+impl Retriever<()> for FakeRow {
+    type RetrieverError = std::convert::Infallible;
+
+    async fn on_retrieve(&mut self, _ctx: &mut (), _id: &str) -> Result<(String, Option<Encoded>, Option<Encoded>, u64), Self::RetrieverError> {
+        Ok((self.state.clone(), self.data.take(), None, self.version))
+    }
+}
+
+#[tokio::test]
+async fn retrieve_restores_the_stored_state_and_version() {
+    let mut ctx = ();
+    let meta = encode(EncodedFormat::Json, &OrderMeta { customer: "fay".to_string() }).unwrap();
+    let retriever = FakeRow { state: "Paid".to_string(), data: Some(meta), version: 5 };
+
+    let order = retrieve(&mut ctx, retriever, "order-7".to_string()).await.unwrap();
+    assert_eq!(order.state(), State::Paid);
+    match order {
+        WrappedOrder::Paid(order) => assert_eq!(order.version(), 5),
+        _ => panic!("expected the retrieved machine to be in Paid")
+    }
+}
+
+#[tokio::test]
+async fn retrieve_surfaces_an_unknown_state_as_invalid_state() {
+    let mut ctx = ();
+    let retriever = FakeRow { state: "NotAState".to_string(), data: None, version: 1 };
+
+    let err = retrieve(&mut ctx, retriever, "order-8".to_string()).await.err().unwrap();
+    assert!(matches!(err, RetrieveError::RestoreError(RestoreError::InvalidState)));
+}
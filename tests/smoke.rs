@@ -0,0 +1,192 @@
+// A minimal end-to-end smoke test: invoke `statemachine!` once and drive the
+// generated machine through init -> transition -> restore. This exists
+// because chunk1-1's `encode()` shipped with a bare `?`-terminated match
+// where the fn signature promised `Result<Encoded, EncodeError>` -- a
+// guaranteed compile error on every invocation that a single compiling
+// example would have caught immediately.
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use statemachine::statemachine;
+
+#[derive(Serialize, Deserialize)]
+struct OrderMeta {
+    customer: String
+}
+
+statemachine! {
+    Order: OrderMeta {
+        init Created {
+            pay => Paid
+        },
+        Paid {
+            ship => Shipped
+        },
+        Shipped {}
+    }
+}
+
+struct NoopObserver;
+
+#[async_trait]
+impl Observer<()> for NoopObserver {
+    type Error = std::convert::Infallible;
+}
+
+#[tokio::test]
+async fn drives_a_machine_end_to_end() {
+    let mut ctx = ();
+    let meta = OrderMeta { customer: "alice".to_string() };
+
+    let order = Order::<Created, (), NoopObserver>::init(&mut ctx, NoopObserver, Some("order-1".to_string()), meta)
+        .await
+        .unwrap();
+    assert_eq!(order.id(), "order-1");
+    assert_eq!(order.version(), 1);
+
+    let order = order.pay(&mut ctx).await.unwrap();
+    assert_eq!(order.version(), 2);
+
+    let order = order.ship(&mut ctx).await.unwrap();
+    assert_eq!(order.version(), 3);
+
+    let encoded_meta = encode(EncodedFormat::Json, &OrderMeta { customer: "alice".to_string() }).unwrap();
+    let restored = restore(NoopObserver, "order-1".to_string(), "Shipped".to_string(), Some(encoded_meta), None, 3)
+        .await
+        .unwrap();
+    assert_eq!(restored.state(), State::Shipped);
+}
+
+// Exercises `State`'s runtime introspection API (chunk0-4) against the graph
+// declared above, rather than against a hand-rolled one, so it breaks if the
+// generated arms and the declared transitions ever disagree.
+#[test]
+fn introspects_the_declared_state_graph() {
+    assert_eq!(State::all(), &[State::Created, State::Paid, State::Shipped]);
+    assert_eq!(State::parse("Paid"), Some(State::Paid));
+    assert_eq!(State::parse("Bogus"), None);
+    assert_eq!(State::Created.valid_events(), &["pay"]);
+    assert_eq!(State::Created.transitions(), &[("pay", State::Paid)]);
+    assert_eq!(State::Shipped.valid_events(), &[] as &[&str]);
+}
+
+// Drives the same machine through `Event`/`WrappedOrder::handle` (chunk0-3)
+// instead of the typestate methods directly, since that's a separate
+// dispatch path with its own `InvalidEvent` failure mode to cover.
+#[tokio::test]
+async fn dispatches_events_through_handle() {
+    let mut ctx = ();
+    let meta = OrderMeta { customer: "bob".to_string() };
+
+    let order = Order::<Created, (), NoopObserver>::init(&mut ctx, NoopObserver, Some("order-2".to_string()), meta)
+        .await
+        .unwrap();
+
+    let wrapped = WrappedOrder::Created(order);
+    assert_eq!(wrapped.state(), State::Created);
+
+    let wrapped = wrapped.handle(&mut ctx, Event::Pay).await.unwrap();
+    assert_eq!(wrapped.state(), State::Paid);
+
+    let wrapped = wrapped.handle(&mut ctx, Event::Ship).await.unwrap();
+    assert_eq!(wrapped.state(), State::Shipped);
+
+    let err = wrapped.handle(&mut ctx, Event::Pay).await.err().unwrap();
+    assert!(matches!(err, TransitionError::InvalidEvent { state: State::Shipped, event: "pay" }));
+}
+
+struct ConflictObserver;
+
+#[async_trait]
+impl Observer<()> for ConflictObserver {
+    type Error = std::convert::Infallible;
+
+    async fn on_transition<T: Serialize + Send, U: Serialize + Send>(&mut self, _ctx: &mut (), _id: &str, _from: State, _to: State, _data: Option<T>, _state_data: Option<U>, _expected_version: u64, _new_version: u64) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+// Covers the optimistic-concurrency path (chunk1-4): an `Observer` reporting
+// zero affected rows must surface as `TransitionError::VersionConflict`
+// rather than the transition silently "succeeding".
+#[tokio::test]
+async fn surfaces_a_version_conflict() {
+    let mut ctx = ();
+    let meta = OrderMeta { customer: "carol".to_string() };
+
+    let order = Order::<Created, (), ConflictObserver>::init(&mut ctx, ConflictObserver, Some("order-3".to_string()), meta)
+        .await
+        .unwrap();
+
+    let err = order.pay(&mut ctx).await.err().unwrap();
+    assert!(matches!(err, TransitionError::VersionConflict));
+}
+
+struct FakeEventLog {
+    events: Vec<(State, Option<Encoded>, Option<Encoded>)>
+}
+
+#[async_trait]
+impl Observer<()> for FakeEventLog {
+    type Error = std::convert::Infallible;
+}
+
+#[async_trait]
+impl EventRetriever<()> for FakeEventLog {
+    type RetrieverError = std::convert::Infallible;
+
+    async fn on_retrieve_events(&mut self, _ctx: &mut (), _id: &str) -> Result<Vec<(State, Option<Encoded>, Option<Encoded>)>, Self::RetrieverError> {
+        Ok(std::mem::take(&mut self.events))
+    }
+}
+
+// Covers `replay`'s empty-log error path (chunk1-6): a retriever with
+// nothing to replay must fail with `ReplayError::EmptyLog` instead of
+// panicking on `events.next()`.
+#[tokio::test]
+async fn replay_errors_on_an_empty_log() {
+    let mut ctx = ();
+    let retriever = FakeEventLog { events: vec![] };
+
+    let err = replay(&mut ctx, retriever, "order-4".to_string(), None, None).await.err().unwrap();
+    assert!(matches!(err, ReplayError::EmptyLog));
+}
+
+// Covers `replay`'s illegal-transition error path (chunk1-6): a log whose
+// second entry isn't reachable from its first via `State::transitions()`
+// (here jumping straight from `Created` to `Shipped`, skipping `Paid`) must
+// fail with `ReplayError::IllegalTransition` instead of silently restoring
+// into a state the declared graph never allows.
+#[tokio::test]
+async fn replay_errors_on_an_illegal_transition() {
+    let mut ctx = ();
+    let meta = encode(EncodedFormat::Json, &OrderMeta { customer: "dave".to_string() }).unwrap();
+    let other_meta = encode(EncodedFormat::Json, &OrderMeta { customer: "dave".to_string() }).unwrap();
+    let retriever = FakeEventLog {
+        events: vec![
+            (State::Created, Some(meta), None),
+            (State::Shipped, Some(other_meta), None)
+        ]
+    };
+
+    let err = replay(&mut ctx, retriever, "order-5".to_string(), None, None).await.err().unwrap();
+    assert!(matches!(err, ReplayError::IllegalTransition { from: State::Created, to: State::Shipped }));
+}
+
+// Covers the fix for chunk1-6's hardcoded `version = 1`: when the retriever's
+// tail starts after a compacted checkpoint rather than at genesis, `replay`
+// must take that starting version from the caller instead of assuming 1.
+#[tokio::test]
+async fn replay_resumes_from_a_caller_supplied_version() {
+    let mut ctx = ();
+    let meta = encode(EncodedFormat::Json, &OrderMeta { customer: "erin".to_string() }).unwrap();
+    let retriever = FakeEventLog { events: vec![(State::Paid, Some(meta), None)] };
+
+    let restored = replay(&mut ctx, retriever, "order-6".to_string(), None, Some(7)).await.unwrap();
+    assert_eq!(restored.state(), State::Paid);
+    match restored {
+        WrappedOrder::Paid(order) => assert_eq!(order.version(), 7),
+        _ => panic!("expected the replayed machine to be in Paid")
+    }
+}
@@ -0,0 +1,79 @@
+// Covers the `tracing` instrumentation (chunk1-5): driving a transition
+// should enter a span named after the transition method. Only compiles with
+// the `tracing` feature, same as the generated `#[tracing::instrument(...)]`
+// attributes it exercises -- run with `cargo test --features tracing`.
+#![cfg(feature = "tracing")]
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use statemachine::statemachine;
+use tracing::span::{Attributes, Id, Record};
+use tracing::Metadata;
+
+#[derive(Serialize, Deserialize)]
+struct OrderMeta {
+    customer: String
+}
+
+statemachine! {
+    Order: OrderMeta {
+        init Created {
+            pay => Paid
+        },
+        Paid {}
+    }
+}
+
+struct NoopObserver;
+
+#[async_trait]
+impl Observer<()> for NoopObserver {
+    type Error = std::convert::Infallible;
+}
+
+// A minimal `tracing::Subscriber` that just records the name of every span
+// created, enough to assert the generated `#[tracing::instrument]` attribute
+// is actually wired up rather than silently a no-op.
+struct RecordingSubscriber {
+    next_id: AtomicU64,
+    names: Arc<Mutex<Vec<String>>>
+}
+
+impl tracing::Subscriber for RecordingSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        self.names.lock().unwrap().push(attrs.metadata().name().to_string());
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+    fn event(&self, _event: &tracing::Event<'_>) {}
+    fn enter(&self, _span: &Id) {}
+    fn exit(&self, _span: &Id) {}
+}
+
+#[tokio::test]
+async fn transition_method_emits_a_tracing_span() {
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = RecordingSubscriber { next_id: AtomicU64::new(0), names: names.clone() };
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut ctx = ();
+    let meta = OrderMeta { customer: "gina".to_string() };
+
+    let order = Order::<Created, (), NoopObserver>::init(&mut ctx, NoopObserver, Some("order-9".to_string()), meta)
+        .await
+        .unwrap();
+    order.pay(&mut ctx).await.unwrap();
+
+    let names = names.lock().unwrap();
+    assert!(names.iter().any(|n| n == "pay"), "expected a span named after the transition method, got {:?}", names);
+}